@@ -0,0 +1,148 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use crate::Color;
+
+/// Where an annotation's text anchor sits, either as a named relative
+/// position or an absolute pixel offset from the top-left corner.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+    Center,
+    Absolute { x: i32, y: i32 },
+}
+
+/// Which palette entry (or literal color) an annotation should be drawn with.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorSource {
+    Primary,
+    Secondary,
+    Rgba([u8; 4]),
+}
+
+impl ColorSource {
+    pub fn resolve(&self, color: &Color) -> [u8; 4] {
+        match self {
+            ColorSource::Primary => color.primary,
+            ColorSource::Secondary => color.secondary,
+            ColorSource::Rgba(rgba) => *rgba,
+        }
+    }
+}
+
+/// A single piece of text to draw over the joined image.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Annotation {
+    pub text: String,
+    pub anchor: Anchor,
+    #[serde(default)]
+    pub font_path: Option<PathBuf>,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    pub color: ColorSource,
+}
+
+fn default_scale() -> f32 {
+    250.0
+}
+
+/// On-disk shape of an `--annotations` config file: a JSON object whose
+/// `annotations` field holds the list of annotations.
+#[derive(Debug, Deserialize)]
+pub struct AnnotationConfig {
+    pub annotations: Vec<Annotation>,
+}
+
+/// The annotations baked in before this feature existed: "20" at (380, 3035)
+/// in the primary color and "19" at (660, 3035) in the secondary color.
+pub fn default_annotations() -> Vec<Annotation> {
+    vec![
+        Annotation {
+            text: "20".to_string(),
+            anchor: Anchor::Absolute { x: 380, y: 580 + 2455 },
+            font_path: None,
+            scale: default_scale(),
+            color: ColorSource::Primary,
+        },
+        Annotation {
+            text: "19".to_string(),
+            anchor: Anchor::Absolute { x: 660, y: 580 + 2455 },
+            font_path: None,
+            scale: default_scale(),
+            color: ColorSource::Secondary,
+        },
+    ]
+}
+
+/// Resolve an anchor to absolute top-left pixel coordinates for a piece of
+/// text, given the canvas size and the rendered text's measured extent.
+/// Clamped at 0: `imageproc::drawing::draw_text_mut` in this crate's
+/// imageproc version takes unsigned coordinates, so an anchor/extent
+/// combination that would go negative is pinned to the edge instead.
+pub fn resolve_anchor(anchor: &Anchor, canvas: (u32, u32), text_extent: (u32, u32)) -> (u32, u32) {
+    let (cw, ch) = (canvas.0 as i32, canvas.1 as i32);
+    let (tw, th) = (text_extent.0 as i32, text_extent.1 as i32);
+    let (x, y) = match *anchor {
+        Anchor::Absolute { x, y } => (x, y),
+        Anchor::TopLeft => (0, 0),
+        Anchor::TopCenter => ((cw - tw) / 2, 0),
+        Anchor::TopRight => (cw - tw, 0),
+        Anchor::BottomLeft => (0, ch - th),
+        Anchor::BottomCenter => ((cw - tw) / 2, ch - th),
+        Anchor::BottomRight => (cw - tw, ch - th),
+        Anchor::Center => ((cw - tw) / 2, (ch - th) / 2),
+    };
+    (x.max(0) as u32, y.max(0) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolute_anchor_ignores_canvas_and_extent() {
+        let anchor = Anchor::Absolute { x: 42, y: 7 };
+        assert_eq!(resolve_anchor(&anchor, (1000, 1000), (300, 50)), (42u32, 7u32));
+    }
+
+    #[test]
+    fn center_anchor_centers_text_on_canvas() {
+        assert_eq!(
+            resolve_anchor(&Anchor::Center, (200, 100), (50, 20)),
+            (75u32, 40u32)
+        );
+    }
+
+    #[test]
+    fn bottom_right_anchor_hugs_the_far_corner() {
+        assert_eq!(
+            resolve_anchor(&Anchor::BottomRight, (200, 100), (50, 20)),
+            (150u32, 80u32)
+        );
+    }
+
+    #[test]
+    fn top_center_anchor_centers_horizontally_at_the_top() {
+        assert_eq!(
+            resolve_anchor(&Anchor::TopCenter, (200, 100), (50, 20)),
+            (75u32, 0u32)
+        );
+    }
+
+    #[test]
+    fn negative_anchor_clamps_to_zero() {
+        // Text wider than the canvas would push top-right negative; it
+        // should clamp to 0 instead of underflowing the unsigned return type.
+        assert_eq!(
+            resolve_anchor(&Anchor::TopRight, (50, 50), (80, 20)),
+            (0u32, 0u32)
+        );
+    }
+}
@@ -0,0 +1,89 @@
+/// Relative luminance per the WCAG 2.x definition: linearize each channel,
+/// then weight by L = 0.2126*R + 0.7152*G + 0.0722*B.
+fn relative_luminance(rgb: [u8; 3]) -> f64 {
+    let linearize = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let [r, g, b] = rgb.map(linearize);
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// WCAG contrast ratio between two colors, always >= 1.0.
+pub fn contrast_ratio(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let (l1, l2) = (relative_luminance(a), relative_luminance(b));
+    let (lighter, darker) = if l1 >= l2 { (l1, l2) } else { (l2, l1) };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+/// Picks a dominant `primary` color and a `secondary` that's guaranteed to
+/// be legible against it, from a palette and how many pixels landed in each
+/// entry. `population[i]` must correspond to `palette[i]`.
+pub fn pick_primary_secondary(palette: &[[u8; 3]], population: &[usize]) -> ([u8; 3], [u8; 3]) {
+    const MIN_CONTRAST: f64 = 4.5;
+
+    let mut ranked: Vec<usize> = (0..palette.len()).collect();
+    ranked.sort_by_key(|&i| std::cmp::Reverse(population[i]));
+
+    let primary = palette[ranked[0]];
+    let secondary = ranked[1..]
+        .iter()
+        .map(|&i| palette[i])
+        .find(|&candidate| contrast_ratio(primary, candidate) >= MIN_CONTRAST)
+        .unwrap_or_else(|| {
+            let black = [0, 0, 0];
+            let white = [255, 255, 255];
+            if contrast_ratio(primary, black) >= contrast_ratio(primary, white) {
+                black
+            } else {
+                white
+            }
+        });
+
+    (primary, secondary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contrast_ratio_is_symmetric_and_bottoms_out_at_one() {
+        assert!((contrast_ratio([0, 0, 0], [0, 0, 0]) - 1.0).abs() < 1e-9);
+        assert!((contrast_ratio([10, 20, 30], [200, 210, 220])
+            - contrast_ratio([200, 210, 220], [10, 20, 30]))
+        .abs()
+            < 1e-9);
+    }
+
+    #[test]
+    fn contrast_ratio_black_vs_white_is_max() {
+        // WCAG's canonical black/white contrast ratio is 21:1.
+        assert!((contrast_ratio([0, 0, 0], [255, 255, 255]) - 21.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pick_primary_secondary_prefers_most_populous_as_primary() {
+        let palette = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+        let population = [10, 100, 1];
+        let (primary, _) = pick_primary_secondary(&palette, &population);
+        assert_eq!(primary, [0, 255, 0]);
+    }
+
+    #[test]
+    fn pick_primary_secondary_falls_back_to_black_or_white_when_nothing_qualifies() {
+        // Every remaining cluster is a near-identical mid-gray, so none can
+        // reach the 4.5:1 contrast threshold against the primary (also a
+        // mid-gray) and the fallback has to pick a pure black/white instead.
+        let palette = [[128, 128, 128], [130, 130, 130], [126, 126, 126]];
+        let population = [100, 50, 50];
+        let (primary, secondary) = pick_primary_secondary(&palette, &population);
+        assert_eq!(primary, [128, 128, 128]);
+        assert!(secondary == [0, 0, 0] || secondary == [255, 255, 255]);
+        assert!(contrast_ratio(primary, secondary) > contrast_ratio(primary, [130, 130, 130]));
+    }
+}
@@ -0,0 +1,152 @@
+use image::{jpeg::JPEGEncoder, ColorType, DynamicImage, Rgb, RgbImage, RgbaImage};
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::AppError;
+
+/// Output image formats the pipeline knows how to encode. WebP is
+/// deliberately absent: the `image` crate version this tool is written
+/// against can only decode WebP, not encode it, so accepting it as a
+/// `--format` value would just fail (or silently produce the wrong format)
+/// at save time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Jpeg,
+    Tiff,
+}
+
+impl FromStr for Format {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(Format::Png),
+            "jpeg" | "jpg" => Ok(Format::Jpeg),
+            "tiff" | "tif" => Ok(Format::Tiff),
+            "webp" => Err(AppError::invalid_option(
+                "webp (this tool can only decode WebP, not encode it)",
+            )),
+            _ => Err(AppError::invalid_option(s)),
+        }
+    }
+}
+
+impl Format {
+    /// Guess a format from a path's extension, defaulting to PNG (the
+    /// original behavior) when the extension is missing or unrecognized.
+    pub fn from_extension(path: &Path) -> Format {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| Format::from_str(ext).ok())
+            .unwrap_or(Format::Png)
+    }
+
+    /// The file extension `save` actually writes for this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Jpeg => "jpg",
+            Format::Tiff => "tiff",
+        }
+    }
+}
+
+/// Quality (1-100) and an optional explicit format override for the
+/// centralized save step, shared by the join and annotation passes so both
+/// encode the same way.
+#[derive(Debug, Clone, Copy)]
+pub struct EncodeOptions {
+    pub format: Option<Format>,
+    pub quality: u8,
+}
+
+/// Save `img` to `path`, picking the format from `options.format` or, failing
+/// that, the path's extension. JPEG is encoded directly; the PNG and TIFF
+/// branches go through `image`'s path-based saving, which chooses the encoder
+/// from the path's extension, so for those the written format follows the
+/// extension rather than `options.format`. JPEG has no alpha channel, so it's
+/// blended onto a white background first; quality only applies to lossy formats.
+pub fn save(img: &RgbaImage, path: &Path, options: &EncodeOptions) -> Result<(), AppError> {
+    let format = options.format.unwrap_or_else(|| Format::from_extension(path));
+
+    match format {
+        Format::Jpeg => {
+            let rgb = flatten_onto_white(img);
+            let mut file = File::create(path).map_err(|e| AppError::io(path, e))?;
+            JPEGEncoder::new_with_quality(&mut file, options.quality)
+                .encode(&rgb, rgb.width(), rgb.height(), ColorType::RGB(8))
+                .map_err(|e| AppError::io(path, e))?;
+        }
+        Format::Png => {
+            image::save_buffer(path, img, img.width(), img.height(), ColorType::RGBA(8))
+                .map_err(|e| AppError::io(path, e))?;
+        }
+        Format::Tiff => {
+            DynamicImage::ImageRgba8(img.clone())
+                .save(path)
+                .map_err(|e| AppError::io(path, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Composite an RGBA image onto an opaque white background, alpha-blending
+/// each channel rather than just truncating away the alpha channel.
+fn flatten_onto_white(img: &RgbaImage) -> RgbImage {
+    let mut rgb = RgbImage::new(img.width(), img.height());
+    for (src, dst) in img.pixels().zip(rgb.pixels_mut()) {
+        let [r, g, b, a] = src.0;
+        let a = a as u32;
+        let blend = |channel: u8| (channel as u32 * a + 255 * (255 - a)) / 255;
+        *dst = Rgb([blend(r) as u8, blend(g) as u8, blend(b) as u8]);
+    }
+    rgb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn flatten_onto_white_leaves_opaque_pixels_untouched() {
+        let mut img = RgbaImage::new(1, 1);
+        img.put_pixel(0, 0, Rgba([10, 20, 30, 255]));
+        let rgb = flatten_onto_white(&img);
+        assert_eq!(*rgb.get_pixel(0, 0), Rgb([10, 20, 30]));
+    }
+
+    #[test]
+    fn flatten_onto_white_fully_transparent_pixel_becomes_white() {
+        let mut img = RgbaImage::new(1, 1);
+        img.put_pixel(0, 0, Rgba([10, 20, 30, 0]));
+        let rgb = flatten_onto_white(&img);
+        assert_eq!(*rgb.get_pixel(0, 0), Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn from_str_accepts_known_formats_and_rejects_webp() {
+        assert_eq!(Format::from_str("png").unwrap(), Format::Png);
+        assert_eq!(Format::from_str("JPEG").unwrap(), Format::Jpeg);
+        assert_eq!(Format::from_str("tif").unwrap(), Format::Tiff);
+        assert!(Format::from_str("webp").is_err());
+        assert!(Format::from_str("bogus").is_err());
+    }
+
+    #[test]
+    fn from_extension_falls_back_to_png() {
+        assert_eq!(Format::from_extension(Path::new("out.jpg")), Format::Jpeg);
+        assert_eq!(Format::from_extension(Path::new("out.tiff")), Format::Tiff);
+        assert_eq!(Format::from_extension(Path::new("out")), Format::Png);
+        assert_eq!(Format::from_extension(Path::new("out.bogus")), Format::Png);
+    }
+
+    #[test]
+    fn extension_matches_what_save_actually_writes() {
+        assert_eq!(Format::Png.extension(), "png");
+        assert_eq!(Format::Jpeg.extension(), "jpg");
+        assert_eq!(Format::Tiff.extension(), "tiff");
+    }
+}
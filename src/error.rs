@@ -0,0 +1,131 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Every fallible operation in the pipeline, pinned to the file that caused
+/// it so a failure in one image doesn't read as "something bad went down"
+/// with no way to tell which input was at fault. `InvalidOption` is the one
+/// variant without a path: it is about a command-line value. The underlying
+/// sources are `Arc`-wrapped so one error (e.g. a palette lookup that fails
+/// for an image used across many output pairs) can be reported once per
+/// affected pair without re-running the fallible step.
+#[derive(Debug, Clone)]
+pub enum AppError {
+    Image {
+        path: PathBuf,
+        source: Arc<image::ImageError>,
+    },
+    Io {
+        path: PathBuf,
+        source: Arc<std::io::Error>,
+    },
+    MismatchSize {
+        path: PathBuf,
+        expected: u32,
+        found: u32,
+    },
+    Font {
+        path: PathBuf,
+        message: String,
+    },
+    Config {
+        path: PathBuf,
+        message: String,
+    },
+    InvalidOption {
+        value: String,
+    },
+}
+
+impl AppError {
+    pub fn image(path: impl Into<PathBuf>, source: image::ImageError) -> Self {
+        AppError::Image {
+            path: path.into(),
+            source: Arc::new(source),
+        }
+    }
+
+    pub fn io(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+        AppError::Io {
+            path: path.into(),
+            source: Arc::new(source),
+        }
+    }
+
+    pub fn font(path: impl Into<PathBuf>, message: impl Into<String>) -> Self {
+        AppError::Font {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn config(path: impl Into<PathBuf>, message: impl Into<String>) -> Self {
+        AppError::Config {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_option(value: impl Into<String>) -> Self {
+        AppError::InvalidOption {
+            value: value.into(),
+        }
+    }
+
+    /// The file this error is about, when it has one.
+    pub fn path(&self) -> Option<&std::path::Path> {
+        match self {
+            AppError::Image { path, .. } => Some(path),
+            AppError::Io { path, .. } => Some(path),
+            AppError::MismatchSize { path, .. } => Some(path),
+            AppError::Font { path, .. } => Some(path),
+            AppError::Config { path, .. } => Some(path),
+            AppError::InvalidOption { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppError::Image { path, source } => {
+                write!(f, "{}: couldn't process image: {}", path.display(), source)
+            }
+            AppError::Io { path, source } => {
+                write!(f, "{}: i/o error: {}", path.display(), source)
+            }
+            AppError::MismatchSize {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: expected size {} to match the rest of the batch, found {}",
+                path.display(),
+                expected,
+                found
+            ),
+            AppError::Font { path, message } => {
+                write!(f, "{}: couldn't parse font: {}", path.display(), message)
+            }
+            AppError::Config { path, message } => {
+                write!(f, "{}: couldn't parse config: {}", path.display(), message)
+            }
+            AppError::InvalidOption { value } => write!(f, "invalid option: {}", value),
+        }
+    }
+}
+
+impl StdError for AppError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            AppError::Image { source, .. } => Some(source.as_ref()),
+            AppError::Io { source, .. } => Some(source.as_ref()),
+            AppError::MismatchSize { .. }
+            | AppError::Font { .. }
+            | AppError::Config { .. }
+            | AppError::InvalidOption { .. } => None,
+        }
+    }
+}
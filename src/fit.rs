@@ -0,0 +1,180 @@
+use image::{imageops, FilterType, Rgba, RgbaImage};
+use std::str::FromStr;
+
+use crate::layout::Layout;
+use crate::error::AppError;
+
+/// How to reconcile images whose dimensions don't already match on the axis
+/// a given `Layout` needs them to agree on.
+#[derive(Debug, Clone, Copy)]
+pub enum Fit {
+    ScaleToMin,
+    ScaleToMax,
+    Pad,
+}
+
+impl FromStr for Fit {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scale-to-min" => Ok(Fit::ScaleToMin),
+            "scale-to-max" => Ok(Fit::ScaleToMax),
+            "pad" => Ok(Fit::Pad),
+            _ => Err(AppError::invalid_option(s)),
+        }
+    }
+}
+
+/// Wrapper so `image::FilterType` can be parsed from a structopt flag.
+#[derive(Debug, Clone, Copy)]
+pub struct Filter(pub FilterType);
+
+impl FromStr for Filter {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Filter(FilterType::Nearest)),
+            "triangle" => Ok(Filter(FilterType::Triangle)),
+            "catmull-rom" => Ok(Filter(FilterType::CatmullRom)),
+            "lanczos3" => Ok(Filter(FilterType::Lanczos3)),
+            _ => Err(AppError::invalid_option(s)),
+        }
+    }
+}
+
+/// Which dimension a `Layout` needs images to agree on before joining.
+fn matching_axis(layout: Layout) -> Option<MatchAxis> {
+    match layout {
+        Layout::Vertical => Some(MatchAxis::Width),
+        Layout::Horizontal => Some(MatchAxis::Height),
+        Layout::Grid { .. } => None,
+    }
+}
+
+enum MatchAxis {
+    Width,
+    Height,
+}
+
+/// Reconcile `images` so they agree on the axis `layout` needs, using `fit`.
+/// A no-op when `fit` is `None` or `layout` doesn't require agreement (grid).
+pub fn apply(
+    images: Vec<RgbaImage>,
+    layout: Layout,
+    fit: Option<Fit>,
+    filter: Filter,
+    background: Rgba<u8>,
+) -> Vec<RgbaImage> {
+    let (fit, axis) = match (fit, matching_axis(layout)) {
+        (Some(fit), Some(axis)) => (fit, axis),
+        _ => return images,
+    };
+
+    let extents: Vec<u32> = images
+        .iter()
+        .map(|img| match axis {
+            MatchAxis::Width => img.width(),
+            MatchAxis::Height => img.height(),
+        })
+        .collect();
+    let target = match fit {
+        Fit::ScaleToMin => extents.iter().copied().min().unwrap_or(0),
+        Fit::ScaleToMax => extents.iter().copied().max().unwrap_or(0),
+        Fit::Pad => extents.iter().copied().max().unwrap_or(0),
+    };
+
+    images
+        .into_iter()
+        .map(|img| match fit {
+            Fit::ScaleToMin | Fit::ScaleToMax => scale_to(&img, &axis, target, filter),
+            Fit::Pad => pad_to(&img, &axis, target, background),
+        })
+        .collect()
+}
+
+fn scale_to(img: &RgbaImage, axis: &MatchAxis, target: u32, filter: Filter) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    let (new_width, new_height) = match axis {
+        MatchAxis::Width => (target, (height as u64 * target as u64 / width as u64) as u32),
+        MatchAxis::Height => (
+            (width as u64 * target as u64 / height as u64) as u32,
+            target,
+        ),
+    };
+    imageops::resize(img, new_width.max(1), new_height.max(1), filter.0)
+}
+
+fn pad_to(img: &RgbaImage, axis: &MatchAxis, target: u32, background: Rgba<u8>) -> RgbaImage {
+    let (width, height) = img.dimensions();
+    let (canvas_width, canvas_height) = match axis {
+        MatchAxis::Width => (target, height),
+        MatchAxis::Height => (width, target),
+    };
+    let mut canvas = RgbaImage::from_pixel(canvas_width, canvas_height, background);
+    let x_offset = (canvas_width - width) / 2;
+    let y_offset = (canvas_height - height) / 2;
+    for y in 0..height {
+        for x in 0..width {
+            canvas.put_pixel(x_offset + x, y_offset + y, *img.get_pixel(x, y));
+        }
+    }
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_to_width_preserves_aspect_ratio() {
+        let img = RgbaImage::from_pixel(100, 50, Rgba([0, 0, 0, 255]));
+        let scaled = scale_to(&img, &MatchAxis::Width, 200, Filter(FilterType::Nearest));
+        assert_eq!(scaled.dimensions(), (200, 100));
+    }
+
+    #[test]
+    fn scale_to_height_preserves_aspect_ratio() {
+        let img = RgbaImage::from_pixel(100, 50, Rgba([0, 0, 0, 255]));
+        let scaled = scale_to(&img, &MatchAxis::Height, 200, Filter(FilterType::Nearest));
+        assert_eq!(scaled.dimensions(), (400, 200));
+    }
+
+    #[test]
+    fn pad_to_width_centers_the_image_and_fills_background() {
+        let img = RgbaImage::from_pixel(10, 10, Rgba([255, 0, 0, 255]));
+        let background = Rgba([9, 9, 9, 9]);
+        let padded = pad_to(&img, &MatchAxis::Width, 30, background);
+        assert_eq!(padded.dimensions(), (30, 10));
+        assert_eq!(*padded.get_pixel(0, 0), background);
+        assert_eq!(*padded.get_pixel(10, 0), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn apply_is_a_noop_without_fit_or_for_grid_layout() {
+        let images = vec![
+            RgbaImage::from_pixel(10, 10, Rgba([0, 0, 0, 255])),
+            RgbaImage::from_pixel(20, 20, Rgba([0, 0, 0, 255])),
+        ];
+        let result = apply(
+            images.clone(),
+            Layout::Grid { cols: 2 },
+            Some(Fit::ScaleToMin),
+            Filter(FilterType::Nearest),
+            Rgba([0, 0, 0, 0]),
+        );
+        assert_eq!(result[0].dimensions(), images[0].dimensions());
+        assert_eq!(result[1].dimensions(), images[1].dimensions());
+
+        let result = apply(
+            images.clone(),
+            Layout::Vertical,
+            None,
+            Filter(FilterType::Nearest),
+            Rgba([0, 0, 0, 0]),
+        );
+        assert_eq!(result[0].dimensions(), images[0].dimensions());
+        assert_eq!(result[1].dimensions(), images[1].dimensions());
+    }
+}
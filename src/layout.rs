@@ -0,0 +1,180 @@
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::AppError;
+
+/// How a batch of images should be combined into one canvas.
+#[derive(Debug, Clone, Copy)]
+pub enum Layout {
+    Vertical,
+    Horizontal,
+    Grid { cols: u32 },
+}
+
+impl FromStr for Layout {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(cols) = s.strip_prefix("grid:") {
+            let cols: u32 = cols
+                .parse()
+                .map_err(|_| AppError::invalid_option(s))?;
+            return Ok(Layout::Grid { cols });
+        }
+        match s {
+            "vertical" => Ok(Layout::Vertical),
+            "horizontal" => Ok(Layout::Horizontal),
+            _ => Err(AppError::invalid_option(s)),
+        }
+    }
+}
+
+/// Join a batch of images according to `layout`. Vertical/horizontal require
+/// the images to already agree on the axis perpendicular to stacking (as
+/// before); grid tiles images of varying sizes, padding each cell to the max
+/// extent with `background`. `paths` must line up with `images` so a
+/// mismatch can be reported against the offending file.
+pub fn join(
+    paths: &[&Path],
+    images: &[RgbaImage],
+    layout: Layout,
+    background: Rgba<u8>,
+) -> Result<RgbaImage, AppError> {
+    match layout {
+        Layout::Vertical => join_along(paths, images, Axis::Width),
+        Layout::Horizontal => join_along(paths, images, Axis::Height),
+        Layout::Grid { cols } => Ok(join_grid(images, cols, background)),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Axis {
+    Width,
+    Height,
+}
+
+fn extent(img: &RgbaImage, axis: Axis) -> u32 {
+    match axis {
+        Axis::Width => img.width(),
+        Axis::Height => img.height(),
+    }
+}
+
+fn join_along(paths: &[&Path], images: &[RgbaImage], axis: Axis) -> Result<RgbaImage, AppError> {
+    let expected = extent(&images[0], axis);
+    for (path, img) in paths.iter().zip(images) {
+        let found = extent(img, axis);
+        if found != expected {
+            return Err(AppError::MismatchSize {
+                path: path.to_path_buf(),
+                expected,
+                found,
+            });
+        }
+    }
+
+    Ok(match axis {
+        Axis::Width => {
+            let height: u32 = images.iter().map(|img| img.height()).sum();
+            let mut canvas = RgbaImage::new(expected, height);
+            let mut y_offset = 0;
+            for img in images {
+                copy_into(&mut canvas, img, 0, y_offset);
+                y_offset += img.height();
+            }
+            canvas
+        }
+        Axis::Height => {
+            let width: u32 = images.iter().map(|img| img.width()).sum();
+            let mut canvas = RgbaImage::new(width, expected);
+            let mut x_offset = 0;
+            for img in images {
+                copy_into(&mut canvas, img, x_offset, 0);
+                x_offset += img.width();
+            }
+            canvas
+        }
+    })
+}
+
+fn join_grid(images: &[RgbaImage], cols: u32, background: Rgba<u8>) -> RgbaImage {
+    let cols = cols.max(1);
+    let rows = ((images.len() as u32) + cols - 1) / cols;
+    let cell_width = images.iter().map(|img| img.width()).max().unwrap_or(0);
+    let cell_height = images.iter().map(|img| img.height()).max().unwrap_or(0);
+
+    let mut canvas = RgbaImage::from_pixel(cell_width * cols, cell_height * rows, background);
+    for (i, img) in images.iter().enumerate() {
+        let col = i as u32 % cols;
+        let row = i as u32 / cols;
+        let x_offset = col * cell_width + (cell_width - img.width()) / 2;
+        let y_offset = row * cell_height + (cell_height - img.height()) / 2;
+        copy_into(&mut canvas, img, x_offset, y_offset);
+    }
+    canvas
+}
+
+/// Copy `src` row-by-row into `dst` at the given offset.
+fn copy_into(dst: &mut RgbaImage, src: &RgbaImage, x_offset: u32, y_offset: u32) {
+    for y in 0..src.height() {
+        for x in 0..src.width() {
+            dst.put_pixel(x_offset + x, y_offset + y, *src.get_pixel(x, y));
+        }
+    }
+}
+
+/// Parse a `r,g,b,a` background color, e.g. `255,255,255,255`.
+pub fn parse_background(s: &str) -> Result<Rgba<u8>, AppError> {
+    let parts: Vec<_> = s.split(',').collect();
+    if parts.len() != 4 {
+        return Err(AppError::invalid_option(s));
+    }
+    let mut channels = [0u8; 4];
+    for (i, part) in parts.iter().enumerate() {
+        channels[i] = part.trim().parse().map_err(|_| AppError::invalid_option(s))?;
+    }
+    Ok(Rgba(channels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_grid_sizes_the_canvas_to_cols_and_rows() {
+        let images = vec![
+            RgbaImage::from_pixel(10, 20, Rgba([255, 0, 0, 255])),
+            RgbaImage::from_pixel(10, 20, Rgba([0, 255, 0, 255])),
+            RgbaImage::from_pixel(10, 20, Rgba([0, 0, 255, 255])),
+        ];
+        let canvas = join_grid(&images, 2, Rgba([0, 0, 0, 0]));
+        // 3 images at 2 cols needs 2 rows; cells are all the same 10x20 size.
+        assert_eq!(canvas.dimensions(), (20, 40));
+    }
+
+    #[test]
+    fn join_grid_leaves_unfilled_cells_as_background() {
+        let images = vec![
+            RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255])),
+            RgbaImage::from_pixel(4, 4, Rgba([0, 255, 0, 255])),
+            RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255])),
+        ];
+        let background = Rgba([9, 9, 9, 9]);
+        let canvas = join_grid(&images, 2, background);
+        // The 4th grid cell (row 1, col 1) has no source image.
+        assert_eq!(*canvas.get_pixel(6, 6), background);
+    }
+
+    #[test]
+    fn join_grid_centers_smaller_images_within_their_cell() {
+        let small = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        let large = RgbaImage::from_pixel(6, 6, Rgba([0, 255, 0, 255]));
+        let background = Rgba([0, 0, 0, 0]);
+        let canvas = join_grid(&[small, large], 2, background);
+        // The small image's cell is 6x6 (the max extent); a 2x2 image
+        // centered in it starts at offset (6-2)/2 = 2.
+        assert_eq!(*canvas.get_pixel(2, 2), Rgba([255, 0, 0, 255]));
+        assert_eq!(*canvas.get_pixel(0, 0), background);
+    }
+}
@@ -1,18 +1,35 @@
 extern crate exoquant;
 extern crate image;
 extern crate imageproc;
+extern crate indicatif;
 extern crate rayon;
+extern crate serde;
+extern crate serde_json;
 extern crate structopt;
 
+mod annotation;
+mod color;
+mod encode;
+mod error;
+mod fit;
+mod layout;
+
+use annotation::Annotation;
+use encode::{EncodeOptions, Format};
+use error::AppError;
+use fit::{Fit, Filter};
 use exoquant::{convert_to_indexed, ditherer, optimizer, Color as ExoColor};
-use image::Rgba;
+use image::{Rgba, RgbaImage};
+use indicatif::{ProgressBar, ProgressStyle};
 use imageproc::drawing::draw_text_mut;
+use layout::Layout;
 use rayon::prelude::*;
-use rusttype::{FontCollection, Scale};
-use std::{error::Error, fmt, fs, path::Path};
+use rusttype::{point, FontCollection, Scale};
+use std::{fs, path::Path, path::PathBuf};
 use structopt::StructOpt;
 
 const ALPHA_CHANNEL: u8 = 255;
+const BUNDLED_FONT: &[u8] = include_bytes!("JosefinSans-Thin.ttf");
 
 #[derive(Debug)]
 struct Color {
@@ -30,66 +47,191 @@ struct Cli {
 
     #[structopt(parse(from_os_str))]
     export_path: std::path::PathBuf,
-}
 
-#[derive(Debug)]
-enum AppError {
-    NotFound,
-    MismatchSize,
-    CouldntSaveFile,
-}
+    /// Path to a JSON annotations config (see `annotation::AnnotationConfig`).
+    /// Falls back to the classic "20"/"19" pair when omitted.
+    #[structopt(long, parse(from_os_str))]
+    annotations: Option<std::path::PathBuf>,
 
-impl Error for AppError {}
+    /// How to combine each image/month pair: "vertical", "horizontal", or
+    /// "grid:<cols>".
+    #[structopt(long, default_value = "vertical")]
+    layout: Layout,
 
-impl fmt::Display for AppError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Oh no, something bad went down")
-    }
+    /// Background color used to pad the axis orthogonal to grid cells,
+    /// as "r,g,b,a".
+    #[structopt(long, default_value = "255,255,255,255", parse(try_from_str = layout::parse_background))]
+    background: Rgba<u8>,
+
+    /// How to reconcile images that don't already agree on the axis the
+    /// chosen layout needs: "scale-to-min", "scale-to-max", or "pad".
+    /// Without this, mismatched images are rejected as before.
+    #[structopt(long)]
+    fit: Option<Fit>,
+
+    /// Resize filter used by --fit scale modes: "nearest", "triangle",
+    /// "catmull-rom", or "lanczos3".
+    #[structopt(long, default_value = "triangle")]
+    filter: Filter,
+
+    /// Output format override: "png", "jpeg", or "tiff". Defaults to
+    /// guessing from each export path's extension. WebP is rejected: this
+    /// tool can only decode it, not encode it.
+    #[structopt(long)]
+    format: Option<Format>,
+
+    /// Quality (1-100) for lossy formats like JPEG.
+    #[structopt(long, default_value = "90")]
+    quality: u8,
+
+    /// Disable the progress bar, for scripted/non-interactive runs.
+    #[structopt(long)]
+    quiet: bool,
 }
 
-impl From<image::ImageError> for AppError {
-    fn from(_error: image::ImageError) -> Self {
-        AppError::NotFound
+fn load_annotations(path: &Option<std::path::PathBuf>) -> Result<Vec<Annotation>, AppError> {
+    match path {
+        Some(path) => {
+            let contents = fs::read_to_string(path).map_err(|e| AppError::io(path, e))?;
+            let config: annotation::AnnotationConfig = serde_json::from_str(&contents)
+                .map_err(|e| AppError::config(path, e.to_string()))?;
+            Ok(config.annotations)
+        }
+        None => Ok(annotation::default_annotations()),
     }
 }
 
-impl From<std::io::Error> for AppError {
-    fn from(_error: std::io::Error) -> Self {
-        AppError::CouldntSaveFile
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("{}", error);
+        std::process::exit(1);
     }
 }
 
-fn main() {
+/// List the immediate children of `path`, surfacing directory-read failures
+/// with the offending path attached instead of panicking.
+fn read_dir_paths(path: &Path) -> Result<Vec<PathBuf>, AppError> {
+    fs::read_dir(path)
+        .map_err(|e| AppError::io(path, e))?
+        .map(|entry| entry.map(|entry| entry.path()).map_err(|e| AppError::io(path, e)))
+        .collect()
+}
+
+fn run() -> Result<(), AppError> {
     let paths = Cli::from_args();
+    let annotations = load_annotations(&paths.annotations)?;
 
-    let months: Vec<_> = fs::read_dir(&paths.first_path)
-        .unwrap()
-        .map(|res| res.unwrap().path())
-        .collect();
-    let images: Vec<_> = fs::read_dir(&paths.second_path)
-        .unwrap()
-        .map(|res| res.unwrap().path())
+    let months = read_dir_paths(&paths.first_path)?;
+    let images = read_dir_paths(&paths.second_path)?;
+
+    let encode_options = EncodeOptions {
+        format: paths.format,
+        quality: paths.quality,
+    };
+
+    let total = (images.len() * months.len()) as u64;
+    let progress = if paths.quiet {
+        ProgressBar::hidden()
+    } else {
+        let progress = ProgressBar::new(total);
+        progress.set_style(
+            ProgressStyle::default_bar()
+                .template("{bar:40.cyan/blue} {pos}/{len} ({eta})"),
+        );
+        progress
+    };
+
+    let results: Vec<Result<(), AppError>> = images
+        .par_iter()
+        .flat_map(|image| {
+            let color = match get_color_palette(image.as_path()) {
+                Ok(color) => color,
+                Err(error) => {
+                    progress.inc(months.len() as u64);
+                    // One output pair was expected per month; report all of
+                    // them as failed rather than collapsing to a single
+                    // entry, so the summary's counts match the progress bar.
+                    return vec![Err(error); months.len()];
+                }
+            };
+            months
+                .par_iter()
+                .map(|month| {
+                    let result = process_pair(
+                        image,
+                        month,
+                        &paths.export_path,
+                        &color,
+                        &annotations,
+                        paths.layout,
+                        paths.background,
+                        paths.fit,
+                        paths.filter,
+                        &encode_options,
+                    );
+                    progress.inc(1);
+                    result
+                })
+                .collect::<Vec<_>>()
+        })
         .collect();
 
-    images.par_iter().for_each(|image| {
-        let color = get_color_palette(image.as_path());
-        months.par_iter().for_each(|month| {
-            // file_stem(), file_name() with extension
-            let path = format!(
-                "{}/{}-{}.png",
-                paths.export_path.to_str().unwrap(),
-                month.file_stem().unwrap().to_str().unwrap(),
-                image.file_stem().unwrap().to_str().unwrap()
-            );
-            let image_path = Path::new(&path);
-            join_photos_vertically(image.as_path(), month.as_path(), image_path).unwrap();
-            write_text(image_path, &color);
-        });
-    });
+    let failures: Vec<&AppError> = results.iter().filter_map(|r| r.as_ref().err()).collect();
+    progress.finish_and_clear();
+    for failure in &failures {
+        eprintln!("{}", failure);
+    }
+    println!(
+        "{} succeeded, {} failed out of {}",
+        results.len() - failures.len(),
+        failures.len(),
+        results.len()
+    );
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_pair(
+    image: &PathBuf,
+    month: &PathBuf,
+    export_path: &Path,
+    color: &Color,
+    annotations: &[Annotation],
+    layout: Layout,
+    background: Rgba<u8>,
+    fit: Option<Fit>,
+    filter: Filter,
+    encode_options: &EncodeOptions,
+) -> Result<(), AppError> {
+    // Derive the extension from whatever format we'll actually encode with,
+    // rather than hardcoding one, so a `--format` override doesn't leave
+    // JPEG/TIFF bytes behind a misleading `.png` name.
+    let extension = encode_options
+        .format
+        .unwrap_or_else(|| Format::from_extension(export_path))
+        .extension();
+    let path = format!(
+        "{}/{}-{}.{}",
+        export_path.to_str().unwrap(),
+        month.file_stem().unwrap().to_str().unwrap(),
+        image.file_stem().unwrap().to_str().unwrap(),
+        extension
+    );
+    let image_path = Path::new(&path);
+
+    let canvas = join_photos(
+        &[image.as_path(), month.as_path()],
+        layout,
+        background,
+        fit,
+        filter,
+    )?;
+    let canvas = write_text(canvas, color, annotations)?;
+    encode::save(&canvas, image_path, encode_options)
 }
 
-fn get_color_palette(path: &Path) -> Color {
-    let img = image::open(path).expect("image couldn't be opened!");
+fn get_color_palette(path: &Path) -> Result<Color, AppError> {
+    let img = image::open(path).map_err(|e| AppError::image(path, e))?;
     let img = img.to_rgba();
     let (width, _) = img.dimensions();
     let pixels: Vec<ExoColor> = img
@@ -98,103 +240,102 @@ fn get_color_palette(path: &Path) -> Color {
         .map(|c| ExoColor::new(c[0], c[1], c[2], c[3]))
         .collect();
 
-    let (palette, _) = convert_to_indexed(
+    let (palette, indexed) = convert_to_indexed(
         &pixels,
         width as usize,
         256,
         &optimizer::KMeans,
         &ditherer::FloydSteinberg::new(),
     );
-    // making sure always alpha is 255.
-    let primary = [palette[0].r, palette[0].g, palette[0].b, ALPHA_CHANNEL];
-
-    // random index, convert_to_index returns a Vec of len = 256
-    let secondary = [
-        palette[200].r,
-        palette[200].g,
-        palette[200].b,
-        ALPHA_CHANNEL,
-    ];
-    Color { primary, secondary }
-}
-
-fn join_photos_vertically(
-    first_path: &Path,
-    second_path: &Path,
-    result_path: &Path,
-) -> Result<(), AppError> {
-    let first_img = image::open(first_path)?;
-    let second_img = image::open(second_path)?;
-
-    let first_img = first_img.to_rgba();
-    let second_img = second_img.to_rgba();
-    let first_size = first_img.dimensions();
-    let second_size = second_img.dimensions();
 
-    // check if the width is not the same, kill it!
-    if first_size.0 != second_size.0 {
-        return Err(AppError::MismatchSize);
+    let mut population = vec![0usize; palette.len()];
+    for &index in &indexed {
+        population[index as usize] += 1;
     }
+    let rgb_palette: Vec<[u8; 3]> = palette.iter().map(|c| [c.r, c.g, c.b]).collect();
+    let (primary, secondary) = color::pick_primary_secondary(&rgb_palette, &population);
 
-    // getting the full width.
-    let width = first_size.0;
-    // joining up both heights
-    let height = first_size.1 + second_size.1;
-
-    let mut first_pxs = first_img.into_raw();
-    let second_pxs = second_img.into_raw();
+    // making sure always alpha is 255.
+    Ok(Color {
+        primary: [primary[0], primary[1], primary[2], ALPHA_CHANNEL],
+        secondary: [secondary[0], secondary[1], secondary[2], ALPHA_CHANNEL],
+    })
+}
 
-    first_pxs.extend(second_pxs);
-    let buffer: &[u8] = &first_pxs; // Generate the image data
+/// Open every path and join them per `layout`, returning the combined image
+/// in memory. Saving is handled centrally by `encode::save` once annotations
+/// have also been drawn.
+fn join_photos(
+    paths: &[&Path],
+    layout: Layout,
+    background: Rgba<u8>,
+    fit: Option<Fit>,
+    filter: Filter,
+) -> Result<RgbaImage, AppError> {
+    let images: Vec<RgbaImage> = paths
+        .iter()
+        .map(|path| {
+            image::open(path)
+                .map(|img| img.to_rgba())
+                .map_err(|e| AppError::image(*path, e))
+        })
+        .collect::<Result<_, AppError>>()?;
+    let images = fit::apply(images, layout, fit, filter, background);
 
-    // Save the buffer to result path.
-    image::save_buffer(result_path, buffer, width, height, image::RGBA(8))?;
-    Ok(())
+    layout::join(paths, &images, layout, background)
 }
 
-fn write_text(path: &Path, color: &Color) -> u32 {
-    // image path
-    let path = Path::new(path);
-
-    // create a new image buffer
-    // let mut image = RgbImage::new(800, 800);
-    let mut img = image::open(path).expect("File couldn't be opened!");
+fn write_text(
+    mut img: RgbaImage,
+    color: &Color,
+    annotations: &[Annotation],
+) -> Result<RgbaImage, AppError> {
+    let canvas = img.dimensions();
 
-    // load the font as &[u8]
-    let font = Vec::from(include_bytes!("JosefinSans-Thin.ttf") as &[u8]);
+    for annotation in annotations {
+        let font_path = annotation
+            .font_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("<bundled font>"));
+        let font_bytes = match &annotation.font_path {
+            Some(font_path) => fs::read(font_path).map_err(|e| AppError::io(font_path, e))?,
+            None => Vec::from(BUNDLED_FONT),
+        };
+        let font = FontCollection::from_bytes(font_bytes)
+            .map_err(|e| AppError::font(font_path.clone(), e.to_string()))?
+            .into_font()
+            .map_err(|e| AppError::font(font_path.clone(), e.to_string()))?;
 
-    //  load font.
-    let font = FontCollection::from_bytes(font)
-        .unwrap()
-        .into_font()
-        .unwrap();
+        let scale = Scale {
+            x: annotation.scale,
+            y: annotation.scale,
+        };
+        let extent = measure_text(scale, &font, &annotation.text);
+        let (x, y) = annotation::resolve_anchor(&annotation.anchor, canvas, extent);
+        draw_text_mut(
+            &mut img,
+            Rgba(annotation.color.resolve(color)),
+            x,
+            y,
+            scale,
+            &font,
+            &annotation.text,
+        );
+    }
 
-    let height = 250.0;
-    let scale = Scale {
-        x: height * 1.0,
-        y: height,
-    };
-    draw_text_mut(
-        &mut img,
-        Rgba(color.primary),
-        380,
-        580 + 2455,
-        scale,
-        &font,
-        "20",
-    );
-    draw_text_mut(
-        &mut img,
-        Rgba(color.secondary),
-        660,
-        580 + 2455,
-        scale,
-        &font,
-        "19",
-    );
+    Ok(img)
+}
 
-    match img.save(path) {
-        Ok(_) => 1,
-        Err(_) => 0,
-    }
+/// Measure the pixel extent `text` would occupy when laid out with `font` at
+/// `scale`, by summing glyph advance widths and taking the font's vertical
+/// metrics as the height. Stands in for `imageproc::drawing::text_size`,
+/// which isn't available in the imageproc version this crate targets.
+fn measure_text(scale: Scale, font: &rusttype::Font, text: &str) -> (u32, u32) {
+    let width: f32 = font
+        .layout(text, scale, point(0.0, 0.0))
+        .map(|glyph| glyph.unpositioned().h_metrics().advance_width)
+        .sum();
+    let v_metrics = font.v_metrics(scale);
+    let height = v_metrics.ascent - v_metrics.descent;
+    (width.ceil().max(0.0) as u32, height.ceil().max(0.0) as u32)
 }